@@ -0,0 +1,90 @@
+//! Manages the zip archive that backs an epub.
+
+extern crate zip;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek};
+
+#[derive(Debug)]
+pub struct ArchiveError { pub error: String }
+
+impl Error for ArchiveError {
+    fn description(&self) -> &str { &self.error }
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "ArchiveError: {}", self.error)
+    }
+}
+
+/// A source that can back the zip archive: a local file, an in-memory
+/// buffer, or any other stream that supports random access.
+trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Wraps the zip archive an epub is stored in.
+pub struct EpubArchive {
+    zip: zip::ZipArchive<Box<ReadSeek>>,
+}
+
+impl EpubArchive {
+    /// Opens the epub's zip archive from a filesystem path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file doesn't exist or isn't a valid zip.
+    pub fn new(path: &str) -> Result<EpubArchive, Box<Error>> {
+        let file = try!(File::open(path));
+        EpubArchive::from_reader(file)
+    }
+
+    /// Opens the epub's zip archive from any `Read + Seek` source, e.g. a
+    /// byte buffer received over HTTP, instead of only a filesystem path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the source isn't a valid zip.
+    pub fn from_reader<R: Read + Seek + 'static>(reader: R) -> Result<EpubArchive, Box<Error>> {
+        let boxed: Box<ReadSeek> = Box::new(reader);
+        let zip = try!(zip::ZipArchive::new(boxed));
+        Ok(EpubArchive { zip: zip })
+    }
+
+    /// Returns the contents of `META-INF/container.xml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive has no container file.
+    pub fn get_container_file(&mut self) -> Result<Vec<u8>, Box<Error>> {
+        self.get_entry("META-INF/container.xml")
+    }
+
+    /// Returns the contents of the zip entry at `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry doesn't exist in the archive.
+    pub fn get_entry(&mut self, name: &str) -> Result<Vec<u8>, Box<Error>> {
+        let mut entry = match self.zip.by_name(name) {
+            Ok(e) => e,
+            Err(_) => return Err(Box::new(ArchiveError { error: format!("{} not found in archive", name) }))
+        };
+        let mut content = vec!();
+        try!(entry.read_to_end(&mut content));
+        Ok(content)
+    }
+
+    /// Returns the contents of the zip entry at `name`, as a `String`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry doesn't exist or isn't valid UTF-8.
+    pub fn get_entry_as_str(&mut self, name: &str) -> Result<String, Box<Error>> {
+        let content = try!(self.get_entry(name));
+        let text = try!(String::from_utf8(content).map_err(|e| Box::new(e) as Box<Error>));
+        Ok(text)
+    }
+}
@@ -6,13 +6,17 @@
 extern crate xml;
 extern crate regex;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::io::{Cursor, Read, Seek};
+use std::rc::Rc;
 
 use archive::EpubArchive;
 
 use xmlutils;
+use xmlutils::XMLNode;
 
 #[derive(Debug)]
 pub struct DocError { pub error: String }
@@ -27,6 +31,56 @@ impl fmt::Display for DocError {
     }
 }
 
+/// A single entry of the table of contents.
+///
+/// Built from the EPUB2 NCX `navMap` or, when present, the EPUB3
+/// `nav` document, so the same struct represents either source.
+#[derive(Debug, Clone)]
+pub struct TocItem {
+    /// The text shown to the reader for this entry.
+    pub label: String,
+
+    /// The `src`/`href` this entry points to, resolved against `root_base`
+    /// so it matches a path stored in `resources`.
+    pub content: String,
+
+    /// Reading order of this entry, as given by the NCX `playOrder`
+    /// attribute (or, for EPUB3 nav, its 1-based position within its
+    /// enclosing `ol`, restarting at 1 in each nested list).
+    pub play_order: usize,
+
+    /// Nested entries, from a child `navPoint`/`ol`.
+    pub children: Vec<TocItem>,
+}
+
+/// A single match returned by `EpubDoc::search`.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    /// The spine index the match was found in; feed this into
+    /// `set_current_page` to navigate to it.
+    pub spine_index: usize,
+
+    /// The offset, in chars, of the match within that chapter's plain text.
+    pub char_offset: usize,
+
+    /// A snippet of surrounding text, clamped to char boundaries.
+    pub snippet: String,
+}
+
+/// A `dc:creator` or `dc:contributor` entry, with the attributes that
+/// distinguish authors from editors and give their sort name.
+#[derive(Debug, Clone)]
+pub struct Creator {
+    /// The creator's display name.
+    pub name: String,
+
+    /// The `opf:role` attribute, e.g. `"aut"` for author or `"edt"` for editor.
+    pub role: Option<String>,
+
+    /// The `opf:file-as` attribute, the name as it should be sorted.
+    pub file_as: Option<String>,
+}
+
 /// Struct to control the epub document
 pub struct EpubDoc {
     /// the zip archive
@@ -41,7 +95,10 @@ pub struct EpubDoc {
     /// resource id -> name
     pub resources: HashMap<String, (String, String)>,
 
-    /// The epub metadata stored as key -> value
+    /// The epub metadata stored as key -> values
+    ///
+    /// Kept as a `Vec` because elements like `dc:creator` or `dc:subject`
+    /// commonly repeat; use `get_metadata` for the common single-value case.
     ///
     /// #Examples
     ///
@@ -50,9 +107,28 @@ pub struct EpubDoc {
     /// # let doc = EpubDoc::new("test.epub");
     /// # let doc = doc.unwrap();
     /// let title = doc.metadata.get("title");
-    /// assert_eq!(title.unwrap(), "Todo es mío");
+    /// assert_eq!(title.unwrap()[0], "Todo es mío");
     /// ```
-    pub metadata: HashMap<String, String>,
+    pub metadata: HashMap<String, Vec<String>>,
+
+    /// `dc:creator`/`dc:contributor` entries, with their `opf:role` and
+    /// `opf:file-as` attributes preserved, in document order.
+    pub creators: Vec<Creator>,
+
+    /// The table of contents, as a tree of `TocItem`s in reading order.
+    ///
+    /// Populated during `fill_resources` from the EPUB2 NCX or the
+    /// EPUB3 nav document, whichever the manifest provides.
+    pub toc: Vec<TocItem>,
+
+    /// Plain text of each spine entry already extracted by `search`,
+    /// keyed by spine index, so repeated searches don't re-unzip chapters.
+    text_cache: HashMap<usize, String>,
+
+    /// Set by `new_meta` when `spine`/`resources` were deliberately left
+    /// unpopulated; navigation methods check this and fail clearly instead
+    /// of behaving as though the book has zero chapters.
+    meta_only: bool,
 
     /// root file base path
     pub root_base: String,
@@ -81,10 +157,64 @@ impl EpubDoc {
     /// Returns an error if the epub is broken or if the file doesn't
     /// exists.
     pub fn new(path: &str) -> Result<EpubDoc, Box<Error>> {
-        let mut archive = try!(EpubArchive::new(path));
-        let spine: Vec<String> = vec!();
-        let resources: HashMap<String, (String, String)> = HashMap::new();
+        let archive = try!(EpubArchive::new(path));
+        EpubDoc::from_archive(archive)
+    }
+
+    /// Opens an epub from any `Read + Seek` source instead of a filesystem
+    /// path, e.g. a byte buffer received over HTTP or a network stream.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// use std::fs::File;
+    /// use epub::doc::EpubDoc;
+    ///
+    /// let file = File::open("test.epub").unwrap();
+    /// let doc = EpubDoc::from_reader(file);
+    /// assert!(doc.is_ok());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken.
+    pub fn from_reader<R: Read + Seek + 'static>(reader: R) -> Result<EpubDoc, Box<Error>> {
+        let archive = try!(EpubArchive::from_reader(reader));
+        EpubDoc::from_archive(archive)
+    }
+
+    /// Opens an epub already fully read into memory.
+    ///
+    /// Convenience wrapper around `from_reader` via a `Cursor`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// use std::fs;
+    /// use epub::doc::EpubDoc;
+    ///
+    /// let bytes = fs::read("test.epub").unwrap();
+    /// let doc = EpubDoc::from_bytes(bytes);
+    /// assert!(doc.is_ok());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<EpubDoc, Box<Error>> {
+        EpubDoc::from_reader(Cursor::new(bytes))
+    }
 
+    fn from_archive(archive: EpubArchive) -> Result<EpubDoc, Box<Error>> {
+        let mut doc = try!(EpubDoc::open_archive(archive, false));
+        try!(doc.fill_resources());
+        Ok(doc)
+    }
+
+    /// Opens `archive`, resolves its root file and base path, and returns
+    /// an otherwise-empty `EpubDoc` shell. Shared by `from_archive` and
+    /// `new_meta`, which differ only in what they do with that shell.
+    fn open_archive(mut archive: EpubArchive, meta_only: bool) -> Result<EpubDoc, Box<Error>> {
         let container = try!(archive.get_container_file());
         let root_file = try!(get_root_file(container));
 
@@ -94,21 +224,67 @@ impl EpubDoc {
         let count = iter.len();
         let base_path = if count >= 2 { iter[count - 2] } else { "" };
 
-        let mut doc = EpubDoc {
+        Ok(EpubDoc {
             archive: archive,
-            spine: spine,
-            resources: resources,
+            spine: vec!(),
+            resources: HashMap::new(),
             metadata: HashMap::new(),
+            creators: vec!(),
+            toc: vec!(),
+            text_cache: HashMap::new(),
+            meta_only: meta_only,
             root_file: root_file.clone(),
             root_base: String::from(base_path) + "/",
             current: 0,
-        };
+        })
+    }
 
-        try!(doc.fill_resources());
+    /// Opens the epub file in `path`, parsing only its `<metadata>` block
+    /// and cover reference.
+    ///
+    /// Skips the manifest/spine work `new` does, so `spine` and
+    /// `resources` are left empty and navigation methods (`get_current`,
+    /// `get_current_str`, `get_current_text`, `get_current_id`,
+    /// `get_current_path`, `get_current_mime`, `go_next`, `go_prev`,
+    /// `set_current_page`, and the id-based `get_resource*` methods) return
+    /// an error until the doc is re-opened with `new`. The `*_by_path`
+    /// resource methods still read straight from the archive. Intended for
+    /// cataloguing large collections where only title/author/cover matter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::doc::EpubDoc;
+    ///
+    /// let doc = EpubDoc::new_meta("test.epub");
+    /// assert!(doc.is_ok());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken or if the file doesn't
+    /// exists.
+    pub fn new_meta(path: &str) -> Result<EpubDoc, Box<Error>> {
+        let archive = try!(EpubArchive::new(path));
+        let mut doc = try!(EpubDoc::open_archive(archive, true));
+
+        let root_content = try!(doc.archive.get_entry(&doc.root_file));
+        let xml = xmlutils::XMLReader::new(root_content.as_slice());
+        let root = try!(xml.parse_xml());
+        try!(doc.parse_metadata(&root));
 
         Ok(doc)
     }
 
+    /// Returns an error if this doc was opened with `new_meta`, since
+    /// `spine`/`resources` weren't populated and navigation can't work.
+    fn require_full_load(&self) -> Result<(), DocError> {
+        if self.meta_only {
+            return Err(DocError { error: String::from("doc was opened with new_meta(); re-open with new() to navigate") });
+        }
+        Ok(())
+    }
+
     /// Returns the id of the epub cover.
     ///
     /// The cover is searched in the doc metadata, by the tag <meta name="cover" value"..">
@@ -129,12 +305,30 @@ impl EpubDoc {
     ///
     /// Returns an error if the cover path can't be found.
     pub fn get_cover_id(&self) -> Result<String, Box<Error>> {
-        match self.metadata.get("cover") {
-            Some(id) => Ok(id.to_string()),
+        match self.get_metadata("cover") {
+            Some(id) => Ok(id),
             None => Err(Box::new(DocError { error: String::from("Cover not found") }))
         }
     }
 
+    /// Returns the first value stored for a metadata key.
+    ///
+    /// Convenience wrapper over `metadata` for the common case where a
+    /// tag like `title` or `cover` only ever has one value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub");
+    /// # let doc = doc.unwrap();
+    /// let title = doc.get_metadata("title");
+    /// assert_eq!(title.unwrap(), "Todo es mío");
+    /// ```
+    pub fn get_metadata(&self, key: &str) -> Option<String> {
+        self.metadata.get(key).and_then(|v| v.first()).cloned()
+    }
+
     /// Returns the cover as Vec<u8>
     ///
     /// # Examples
@@ -181,6 +375,7 @@ impl EpubDoc {
     ///
     /// Returns an error if the id doesn't exists in the epub
     pub fn get_resource(&mut self, id: &str) -> Result<Vec<u8>, Box<Error>> {
+        try!(self.require_full_load());
         let path: String = match self.resources.get(id) {
             Some(s) => s.0.to_string(),
             None => return Err(Box::new(DocError { error: String::from("id not found") }))
@@ -205,6 +400,7 @@ impl EpubDoc {
     ///
     /// Returns an error if the id doesn't exists in the epub
     pub fn get_resource_str(&mut self, id: &str) -> Result<String, Box<Error>> {
+        try!(self.require_full_load());
         let path: String = match self.resources.get(id) {
             Some(s) => s.0.to_string(),
             None => return Err(Box::new(DocError { error: String::from("id not found") }))
@@ -213,6 +409,122 @@ impl EpubDoc {
         Ok(content)
     }
 
+    /// Returns the resource content by full path in the epub archive, as
+    /// plain text, stripped of markup.
+    ///
+    /// The XHTML body is walked node by node: text nodes are appended
+    /// verbatim and a newline is emitted when leaving a block-level
+    /// element (`p`, `div`, `br`, `h1`-`h6`, `li`), so paragraph breaks
+    /// survive. `head`, `script` and `style` subtrees are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exists in the epub or the
+    /// resource isn't valid XHTML.
+    pub fn get_resource_text_by_path(&mut self, path: &str) -> Result<String, Box<Error>> {
+        let content = try!(self.archive.get_entry(path));
+        let decoded = decode_entities(&content);
+        let xml = xmlutils::XMLReader::new(decoded.as_bytes());
+        let root = try!(xml.parse_xml());
+        let body = try!(root.borrow().find("body"));
+
+        let mut text = String::new();
+        render_node_text(&body.borrow(), &mut text);
+        Ok(text)
+    }
+
+    /// Returns the resource content by the id defined in the spine, as
+    /// plain text. See `get_resource_text_by_path` for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the id doesn't exists in the epub or the
+    /// resource isn't valid XHTML.
+    pub fn get_resource_text(&mut self, id: &str) -> Result<String, Box<Error>> {
+        try!(self.require_full_load());
+        let path: String = match self.resources.get(id) {
+            Some(s) => s.0.to_string(),
+            None => return Err(Box::new(DocError { error: String::from("id not found") }))
+        };
+        self.get_resource_text_by_path(&path)
+    }
+
+    /// Returns the current chapter content as plain text. See
+    /// `get_resource_text_by_path` for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the current chapter isn't valid XHTML.
+    pub fn get_current_text(&mut self) -> Result<String, Box<Error>> {
+        let current_id = try!(self.get_current_id());
+        self.get_resource_text(&current_id)
+    }
+
+    /// Searches the whole book for `query`, returning a hit for every
+    /// non-overlapping occurrence with enough surrounding text to identify
+    /// it; the scan resumes after the end of each match.
+    ///
+    /// Each spine entry's plain text is extracted at most once and cached,
+    /// so repeated searches don't re-unzip already-visited chapters.
+    ///
+    /// Returns `Result` rather than a bare `Vec` because each chapter is
+    /// parsed lazily on first search and that parse can fail; callers
+    /// shouldn't see a partial, silently-truncated result set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub");
+    /// # let mut doc = doc.unwrap();
+    /// let hits = doc.search("dragon", false).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a spine resource can't be parsed as XHTML.
+    pub fn search(&mut self, query: &str, case_sensitive: bool) -> Result<Vec<SearchHit>, Box<Error>> {
+        const CONTEXT_CHARS: usize = 40;
+
+        let needle: Vec<char> = query.chars().collect();
+        if needle.is_empty() {
+            return Ok(vec!());
+        }
+
+        let mut hits = vec!();
+        for spine_index in 0..self.spine.len() {
+            if !self.text_cache.contains_key(&spine_index) {
+                let id = self.spine[spine_index].clone();
+                let text = try!(self.get_resource_text(&id));
+                self.text_cache.insert(spine_index, text);
+            }
+            let text = self.text_cache.get(&spine_index).unwrap();
+            let chars: Vec<char> = text.chars().collect();
+
+            let mut start = 0;
+            while start + needle.len() <= chars.len() {
+                if chars_match(&chars[start..start + needle.len()], &needle, case_sensitive) {
+                    let char_offset = start;
+                    let ctx_start = char_offset.saturating_sub(CONTEXT_CHARS);
+                    let ctx_end = (char_offset + needle.len() + CONTEXT_CHARS).min(chars.len());
+                    let snippet: String = chars[ctx_start..ctx_end].iter().collect();
+
+                    hits.push(SearchHit {
+                        spine_index: spine_index,
+                        char_offset: char_offset,
+                        snippet: snippet,
+                    });
+
+                    start += needle.len();
+                } else {
+                    start += 1;
+                }
+            }
+        }
+
+        Ok(hits)
+    }
+
     /// Returns the resource mime-type
     ///
     /// # Examples
@@ -228,6 +540,7 @@ impl EpubDoc {
     ///
     /// Fails if the resource can't be found.
     pub fn get_resource_mime(&self, id: &str) -> Result<String, Box<Error>> {
+        try!(self.require_full_load());
         match self.resources.get(id) {
             Some(&(_, ref res)) => return Ok(res.to_string()),
             None => {}
@@ -325,6 +638,7 @@ impl EpubDoc {
     /// assert_eq!("titlepage.xhtml", id.unwrap());
     /// ```
     pub fn get_current_id(&self) -> Result<String, Box<Error>> {
+        try!(self.require_full_load());
         let current_id = self.spine.get(self.current);
         match current_id {
             Some(id) => return Ok(id.to_string()),
@@ -354,6 +668,7 @@ impl EpubDoc {
     ///
     /// If the page is the last, will not change and an error will be returned
     pub fn go_next(&mut self) -> Result<(), DocError> {
+        try!(self.require_full_load());
         if self.current + 1 >= self.spine.len() {
             return Err(DocError { error: String::from("last page") });
         }
@@ -382,6 +697,7 @@ impl EpubDoc {
     ///
     /// If the page is the first, will not change and an error will be returned
     pub fn go_prev(&mut self) -> Result<(), DocError> {
+        try!(self.require_full_load());
         if self.current < 1 {
             return Err(DocError { error: String::from("first page") });
         }
@@ -427,6 +743,7 @@ impl EpubDoc {
     ///
     /// If the page isn't valid, will not change and an error will be returned
     pub fn set_current_page(&mut self, n: usize) -> Result<(), DocError> {
+        try!(self.require_full_load());
         if n >= self.spine.len() {
             return Err(DocError { error: String::from("page not valid") });
         }
@@ -434,6 +751,44 @@ impl EpubDoc {
         Ok(())
     }
 
+    /// Returns the table of contents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub");
+    /// # let doc = doc.unwrap();
+    /// let toc = doc.get_toc();
+    /// ```
+    pub fn get_toc(&self) -> &Vec<TocItem> {
+        &self.toc
+    }
+
+    /// Changes `current` to the spine index backing a `TocItem`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the toc entry's content doesn't match any
+    /// resource present in the spine.
+    pub fn set_current_from_toc(&mut self, item: &TocItem) -> Result<(), DocError> {
+        let path = match item.content.split('#').next() {
+            Some(p) => p.to_string(),
+            None => item.content.clone(),
+        };
+
+        for (i, id) in self.spine.iter().enumerate() {
+            if let Some(&(ref res_path, _)) = self.resources.get(id) {
+                if res_path == &path {
+                    self.current = i;
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(DocError { error: String::from("toc entry not found in spine") })
+    }
+
     fn fill_resources(&mut self) -> Result<(), Box<Error>> {
         let container = try!(self.archive.get_entry(&self.root_file));
         let xml = xmlutils::XMLReader::new(container.as_slice());
@@ -457,23 +812,261 @@ impl EpubDoc {
             self.spine.push(id);
         }
 
-        // metadata
+        try!(self.parse_metadata(&root));
+        self.toc = try!(self.fill_toc(&root));
+
+        Ok(())
+    }
+
+    /// Parses the `<metadata>` block of the root file into `metadata` and
+    /// `creators`. Shared by `fill_resources` and the meta-only fast path.
+    fn parse_metadata(&mut self, root: &Rc<RefCell<XMLNode>>) -> Result<(), Box<Error>> {
         let metadata = try!(root.borrow().find("metadata"));
         for r in metadata.borrow().childs.iter() {
             let item = r.borrow();
             if item.name.local_name == "meta" {
                 let k = try!(item.get_attr("name"));
                 let v = try!(item.get_attr("content"));
-                self.metadata.insert(k, v);
+                self.metadata.entry(k).or_insert_with(Vec::new).push(v);
+            } else if item.name.local_name == "creator" || item.name.local_name == "contributor" {
+                let name = match item.text { Some(ref x) => x.to_string(), None => String::from("") };
+                self.creators.push(Creator {
+                    name: name.clone(),
+                    role: item.get_attr("role").ok(),
+                    file_as: item.get_attr("file-as").ok(),
+                });
+                let ref k = item.name.local_name;
+                self.metadata.entry(k.to_string()).or_insert_with(Vec::new).push(name);
             } else {
                 let ref k = item.name.local_name;
                 let v = match item.text { Some(ref x) => x.to_string(), None => String::from("") };
-                self.metadata.insert(k.to_string(), v);
+                self.metadata.entry(k.to_string()).or_insert_with(Vec::new).push(v);
             }
         }
 
         Ok(())
     }
+
+    /// Locates the NCX (EPUB2) or nav document (EPUB3) from the manifest
+    /// and parses it into a `TocItem` tree.
+    fn fill_toc(&mut self, root: &Rc<RefCell<XMLNode>>) -> Result<Vec<TocItem>, Box<Error>> {
+        let manifest = try!(root.borrow().find("manifest"));
+
+        let mut nav_href: Option<String> = None;
+        let mut ncx_href: Option<String> = None;
+        for r in manifest.borrow().childs.iter() {
+            let item = r.borrow();
+            if let Ok(props) = item.get_attr("properties") {
+                if props.split_whitespace().any(|p| p == "nav") {
+                    nav_href = item.get_attr("href").ok();
+                }
+            }
+            if let Ok(mtype) = item.get_attr("media-type") {
+                if mtype == "application/x-dtbncx+xml" {
+                    ncx_href = item.get_attr("href").ok();
+                }
+            }
+        }
+
+        // A declared-but-broken toc reference shouldn't stop the whole book
+        // from opening, so parse failures here degrade to "no toc" rather
+        // than propagating.
+        if let Some(href) = nav_href {
+            if let Some(items) = self.parse_epub3_nav(&href) {
+                return Ok(items);
+            }
+        }
+
+        if let Some(href) = ncx_href {
+            if let Some(items) = self.parse_ncx(&href) {
+                return Ok(items);
+            }
+        }
+
+        Ok(vec!())
+    }
+
+    fn parse_epub3_nav(&mut self, href: &str) -> Option<Vec<TocItem>> {
+        let path = self.root_base.to_string() + href;
+        let content = self.archive.get_entry(&path).ok()?;
+        let xml = xmlutils::XMLReader::new(content.as_slice());
+        let nav_root = xml.parse_xml().ok()?;
+        let nav = find_epub3_nav_toc(&nav_root).ok()?;
+        let ol = nav.borrow().find("ol").ok()?;
+        let ol = ol.borrow();
+        Some(parse_nav_ol(&ol, &self.root_base))
+    }
+
+    fn parse_ncx(&mut self, href: &str) -> Option<Vec<TocItem>> {
+        let path = self.root_base.to_string() + href;
+        let content = self.archive.get_entry(&path).ok()?;
+        let xml = xmlutils::XMLReader::new(content.as_slice());
+        let ncx_root = xml.parse_xml().ok()?;
+        let nav_map = ncx_root.borrow().find("navMap").ok()?;
+        let nav_map = nav_map.borrow();
+        Some(parse_nav_points(&nav_map, &self.root_base))
+    }
+}
+
+/// Finds the `<nav epub:type="toc">` element among the `nav` elements
+/// directly under the candidate nav document's `body`; `nav` elements
+/// nested deeper are not searched.
+fn find_epub3_nav_toc(root: &Rc<RefCell<XMLNode>>) -> Result<Rc<RefCell<XMLNode>>, Box<Error>> {
+    for body in root.borrow().childs.iter() {
+        if body.borrow().name.local_name != "body" {
+            continue;
+        }
+        for r in body.borrow().childs.iter() {
+            if r.borrow().name.local_name != "nav" {
+                continue;
+            }
+            if let Ok(kind) = r.borrow().get_attr("type") {
+                if kind == "toc" {
+                    return Ok(r.clone());
+                }
+            }
+        }
+    }
+    Err(Box::new(DocError { error: String::from("toc nav not found") }))
+}
+
+/// Recursively converts an EPUB3 nav `<ol>` into a `TocItem` tree.
+///
+/// `content` is resolved against `root_base` so it matches a path stored
+/// in `resources`.
+fn parse_nav_ol(ol: &XMLNode, root_base: &str) -> Vec<TocItem> {
+    let mut items = vec!();
+    for (i, r) in ol.childs.iter().enumerate() {
+        let li = r.borrow();
+        if li.name.local_name != "li" {
+            continue;
+        }
+
+        let mut label = String::new();
+        let mut content = String::new();
+        let mut children = vec!();
+        for c in li.childs.iter() {
+            let child = c.borrow();
+            match child.name.local_name.as_ref() {
+                "a" | "span" => {
+                    label = child.text.clone().unwrap_or_default();
+                    content = root_base.to_string() + &child.get_attr("href").unwrap_or_default();
+                }
+                "ol" => children = parse_nav_ol(&child, root_base),
+                _ => {}
+            }
+        }
+
+        items.push(TocItem {
+            label: label,
+            content: content,
+            play_order: i + 1,
+            children: children,
+        });
+    }
+    items
+}
+
+/// Recursively converts an EPUB2 NCX `<navMap>`/`<navPoint>` into a
+/// `TocItem` tree, ordered by the `playOrder` attribute.
+///
+/// `content` is resolved against `root_base` so it matches a path stored
+/// in `resources`.
+fn parse_nav_points(node: &XMLNode, root_base: &str) -> Vec<TocItem> {
+    let mut items = vec!();
+    for r in node.childs.iter() {
+        let nav_point = r.borrow();
+        if nav_point.name.local_name != "navPoint" {
+            continue;
+        }
+
+        let play_order = nav_point.get_attr("playOrder")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(0);
+
+        let mut label = String::new();
+        let mut content = String::new();
+        for c in nav_point.childs.iter() {
+            let child = c.borrow();
+            match child.name.local_name.as_ref() {
+                "navLabel" => {
+                    if let Ok(text) = child.find("text") {
+                        label = text.borrow().text.clone().unwrap_or_default();
+                    }
+                }
+                "content" => {
+                    content = root_base.to_string() + &child.get_attr("src").unwrap_or_default();
+                }
+                _ => {}
+            }
+        }
+        let children = parse_nav_points(&nav_point, root_base);
+
+        items.push(TocItem {
+            label: label,
+            content: content,
+            play_order: play_order,
+            children: children,
+        });
+    }
+    items.sort_by_key(|item| item.play_order);
+    items
+}
+
+/// Block-level elements that should be followed by a newline once their
+/// subtree has been rendered, so paragraph boundaries survive.
+const BLOCK_TAGS: [&'static str; 9] = ["p", "div", "br", "h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// Appends the rendered text of `node` (and its descendants) to `out`,
+/// skipping `head`, `script` and `style` subtrees and emitting a newline
+/// after block-level elements.
+fn render_node_text(node: &XMLNode, out: &mut String) {
+    let name = node.name.local_name.as_str();
+    if name == "head" || name == "script" || name == "style" {
+        return;
+    }
+
+    if let Some(ref text) = node.text {
+        out.push_str(text);
+    }
+
+    for child in node.childs.iter() {
+        render_node_text(&child.borrow(), out);
+    }
+
+    if BLOCK_TAGS.contains(&name) || name == "li" {
+        out.push('\n');
+    }
+}
+
+/// Decodes the handful of HTML entities (beyond the five XML predefines)
+/// that a strict XML parser would otherwise reject, such as `&nbsp;`.
+fn decode_entities(content: &[u8]) -> String {
+    let text = String::from_utf8_lossy(content);
+    text.replace("&nbsp;", "&#160;")
+        .replace("&mdash;", "&#8212;")
+        .replace("&ndash;", "&#8211;")
+        .replace("&hellip;", "&#8230;")
+        .replace("&copy;", "&#169;")
+        .replace("&rsquo;", "&#8217;")
+        .replace("&lsquo;", "&#8216;")
+        .replace("&rdquo;", "&#8221;")
+        .replace("&ldquo;", "&#8220;")
+}
+
+/// Compares two equal-length char slices, optionally ignoring case.
+///
+/// Case folding via `char::to_lowercase` can expand a single char into
+/// several (e.g. `İ` becomes two chars), so matching is done char-by-char
+/// on iterators rather than by lowercasing and re-joining into a `String`;
+/// that would desynchronize char offsets from the original text.
+fn chars_match(haystack: &[char], needle: &[char], case_sensitive: bool) -> bool {
+    if case_sensitive {
+        haystack == needle
+    } else {
+        haystack.iter().zip(needle.iter()).all(|(&h, &n)| h.to_lowercase().eq(n.to_lowercase()))
+    }
 }
 
 fn get_root_file(container: Vec<u8>) -> Result<String, Box<Error>> {